@@ -1,12 +1,12 @@
-use rmodbus::server::context;
+use rmodbus::server::context::{self, ModbusContext};
 use std::fs::File;
 use std::io::prelude::*;
-use std::sync::MutexGuard;
+use std::sync::{Arc, Mutex};
 
-fn looping() {
+fn looping(shared: Arc<Mutex<ModbusContext>>) {
     loop {
         // READ WORK MODES ETC
-        let mut ctx = context::CONTEXT.lock().unwrap();
+        let mut ctx = shared.lock().unwrap();
         let _param1 = context::get(1000, &ctx.holdings).unwrap();
         let _param2 = context::get_f32(1100, &ctx.holdings).unwrap(); // ieee754 f32
         let _param3 = context::get_u32(1200, &ctx.holdings).unwrap(); // u32
@@ -17,7 +17,7 @@ fn looping() {
             match cmd {
                 1 => {
                     println!("saving memory context");
-                    let _ = save_locked("/tmp/plc1.dat", &ctx).map_err(|_| {
+                    let _ = save("/tmp/plc1.dat", &ctx).map_err(|_| {
                         eprintln!("unable to save context!");
                     });
                 }
@@ -29,22 +29,19 @@ fn looping() {
         // DO SOME JOB
         // ..........
         // WRITE RESULTS
-        let mut ctx = context::CONTEXT.lock().unwrap();
+        let mut ctx = shared.lock().unwrap();
         context::set(0, true, &mut ctx.coils).unwrap();
-        context::set_bulk(10, &(vec![10, 20]), &mut ctx.holdings).unwrap();
+        context::set_bulk(10, &[10, 20], &mut ctx.holdings).unwrap();
         context::set_f32(20, 935.77, &mut ctx.inputs).unwrap();
     }
 }
 
-fn save_locked(
-    fname: &str,
-    ctx: &MutexGuard<context::ModbusContext>,
-) -> Result<(), std::io::Error> {
+fn save(fname: &str, ctx: &ModbusContext) -> Result<(), std::io::Error> {
     let mut file = match File::create(fname) {
         Ok(v) => v,
         Err(e) => return Err(e),
     };
-    match file.write_all(&context::dump_locked(ctx)) {
+    match file.write_all(&context::dump(ctx)) {
         Ok(_) => {}
         Err(e) => return Err(e),
     }
@@ -55,7 +52,7 @@ fn save_locked(
     return Ok(());
 }
 
-fn load(fname: &str) -> Result<(), std::io::Error> {
+fn load(fname: &str, ctx: &mut ModbusContext) -> Result<(), std::io::Error> {
     let mut file = match File::open(fname) {
         Ok(v) => v,
         Err(e) => return Err(e),
@@ -65,7 +62,7 @@ fn load(fname: &str) -> Result<(), std::io::Error> {
         Ok(_) => {}
         Err(e) => return Err(e),
     }
-    context::restore(&data).unwrap();
+    context::restore(&data, ctx).unwrap();
     return Ok(());
 }
 
@@ -75,12 +72,14 @@ mod tcp;
 fn main() {
     // read context
     let unit_id = 1;
-    let _ = load(&"/tmp/plc1.dat").map_err(|_| {
+    let ctx = Arc::new(Mutex::new(ModbusContext::new()));
+    let _ = load(&"/tmp/plc1.dat", &mut ctx.lock().unwrap()).map_err(|_| {
         eprintln!("warning: no saved context");
     });
     use std::thread;
+    let tcp_ctx = ctx.clone();
     thread::spawn(move || {
-        tcp::tcpserver(unit_id, "localhost:5502");
+        tcp::tcpserver(unit_id, "localhost:5502", tcp_ctx);
     });
-    looping();
+    looping(ctx);
 }
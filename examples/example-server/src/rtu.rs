@@ -2,9 +2,12 @@ use serial::prelude::*;
 use std::io::{Read, Write};
 use std::time::Duration;
 
+use rmodbus::rtu::{RtuError, RtuFramer, StdTimeSource};
+use rmodbus::server::context::ModbusContext;
 use rmodbus::server::{ModbusFrame, ModbusProto, process_frame};
 
 pub fn rtuserver(unit: u8, port: &str) {
+    let mut ctx = ModbusContext::new();
     let mut port = serial::open(port).unwrap();
     port.reconfigure(&|settings| {
         (settings.set_baud_rate(serial::Baud9600).unwrap());
@@ -15,20 +18,36 @@ pub fn rtuserver(unit: u8, port: &str) {
         Ok(())
     })
     .unwrap();
-    port.set_timeout(Duration::from_secs(3600)).unwrap();
+    // short read timeout so we can detect the trailing t3.5 silence between frames
+    port.set_timeout(Duration::from_millis(1)).unwrap();
+    let mut framer = RtuFramer::new(9600, StdTimeSource::new());
     loop {
-        let mut buf: ModbusFrame = [0; 256];
-        if port.read(&mut buf).unwrap() > 0 {
-            println!("got frame");
-            let response: Vec<u8> = match process_frame(unit, &buf, ModbusProto::Rtu) {
-                Some(v) => v,
-                None => {
-                    println!("frame drop");
-                    continue;
+        let mut byte = [0u8; 1];
+        match port.read(&mut byte) {
+            Ok(1) => {
+                if let Err(RtuError::CharacterTimeout) = framer.feed(byte[0]) {
+                    println!("character timing error, frame dropped");
                 }
-            };
-            println!("{:x?}", response);
-            port.write(response.as_slice()).unwrap();
+                continue;
+            }
+            // timeout / no byte: fall through to check for a completed frame
+            _ => {}
         }
+        let frame = match framer.poll() {
+            Some(f) => f,
+            None => continue,
+        };
+        println!("got frame");
+        let mut buf: ModbusFrame = [0; 256];
+        buf[..frame.len()].copy_from_slice(frame);
+        let response: Vec<u8> = match process_frame(unit, &buf, ModbusProto::Rtu, &mut ctx) {
+            Some(v) => v,
+            None => {
+                println!("frame drop");
+                continue;
+            }
+        };
+        println!("{:x?}", response);
+        port.write(response.as_slice()).unwrap();
     }
 }
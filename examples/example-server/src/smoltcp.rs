@@ -0,0 +1,67 @@
+// Modbus TCP/UDP endpoint driven by a pure-Rust smoltcp stack, for bare-metal controllers that
+// moved off lwip. Build rmodbus with `default-features = false` so `process_frame` writes its reply
+// into a stack-allocated `ModbusFrameBuf` and allocates nothing.
+//
+// This is illustrative glue: `poll_tcp` / `poll_udp` are called on every iteration of the smoltcp
+// `Interface::poll` loop, with sockets obtained from the application's `SocketSet`.
+
+use smoltcp::socket::tcp;
+use smoltcp::socket::udp;
+
+use rmodbus::server::context::ModbusContext;
+use rmodbus::server::{
+    process_frame, tcp_frame_len, ModbusFrame, ModbusFrameBuf, ModbusProto, ModbusResponse,
+};
+
+/// Service a Modbus/TCP socket
+///
+/// Reads the MBAP header to learn the full frame length before dispatching, so partial reads and
+/// back-to-back frames are handled correctly, and writes the reply straight back through the socket
+/// with no heap allocation.
+pub fn poll_tcp(unit: u8, socket: &mut tcp::Socket, ctx: &mut ModbusContext) {
+    if !socket.can_recv() {
+        return;
+    }
+    // peek the 6-byte MBAP header without consuming it, so we don't dequeue a half frame
+    // (peek_slice / recv_slice also copy across the receive ring's wrap point)
+    let mut header = [0u8; 6];
+    let frame_len = match socket.peek_slice(&mut header) {
+        Ok(6) => match tcp_frame_len(&header) {
+            Some(len) if len <= 256 => len,
+            _ => return,
+        },
+        _ => return,
+    };
+    if socket.recv_queue() < frame_len {
+        return; // whole frame not here yet
+    }
+    let mut frame: ModbusFrame = [0; 256];
+    let mut response = ModbusFrameBuf::new();
+    let taken = socket.recv_slice(&mut frame[..frame_len]).unwrap_or(0);
+    if taken < frame_len {
+        return;
+    }
+    if process_frame(unit, &frame, ModbusProto::TcpUdp, ctx, &mut response).is_some() {
+        let _ = socket.send_slice(response.as_slice());
+    }
+}
+
+/// Service a Modbus/UDP socket (one datagram per frame, no MBAP length peeking needed)
+pub fn poll_udp(unit: u8, socket: &mut udp::Socket, ctx: &mut ModbusContext) {
+    if !socket.can_recv() {
+        return;
+    }
+    let mut frame: ModbusFrame = [0; 256];
+    let endpoint = match socket.recv() {
+        Ok((data, meta)) => {
+            let n = data.len().min(frame.len());
+            frame[..n].copy_from_slice(&data[..n]);
+            meta.endpoint
+        }
+        Err(_) => return,
+    };
+    let mut response = ModbusFrameBuf::new();
+    if process_frame(unit, &frame, ModbusProto::TcpUdp, ctx, &mut response).is_some() {
+        let _ = socket.send_slice(response.as_slice(), endpoint);
+    }
+}
@@ -0,0 +1,299 @@
+/// Maximum number of registers / bits of each kind the context holds
+///
+/// Modbus addresses are 16-bit, but keeping the full 65536-entry maps resident is wasteful for
+/// most slaves, so the context is sized to a fixed, build-time constant. Requests addressing a
+/// register outside this range are answered with an illegal-data-address error (0x02).
+pub const CONTEXT_SIZE: usize = 10000;
+
+/// Device identification objects returned by function 0x2B / 0x0E
+///
+/// The three basic objects are mandatory in the Modbus spec; their values are device-specific and
+/// therefore configurable. Stored as `&'static str` so the struct needs no allocation.
+#[derive(Copy, Clone, Default)]
+pub struct DeviceIdentification {
+    pub vendor_name: &'static str,
+    pub product_code: &'static str,
+    pub major_minor_revision: &'static str,
+}
+
+/// Register context of a single Modbus unit
+///
+/// Holds the four standard Modbus data banks plus the device-level metadata a slave reports back
+/// (exception status, identification). The struct is a plain value with no heap-allocated members,
+/// so it can live on the stack (or in a `static`) on heap-less targets as well.
+pub struct ModbusContext {
+    pub coils: [bool; CONTEXT_SIZE],
+    pub discretes: [bool; CONTEXT_SIZE],
+    pub holdings: [u16; CONTEXT_SIZE],
+    pub inputs: [u16; CONTEXT_SIZE],
+    /// Value reported by Read Exception Status (0x07)
+    pub exception_status: u8,
+    /// Strings reported by Read Device Identification (0x2B / 0x0E)
+    pub identification: DeviceIdentification,
+}
+
+impl ModbusContext {
+    /// Create a new, zeroed register context
+    pub fn new() -> Self {
+        ModbusContext {
+            coils: [false; CONTEXT_SIZE],
+            discretes: [false; CONTEXT_SIZE],
+            holdings: [0; CONTEXT_SIZE],
+            inputs: [0; CONTEXT_SIZE],
+            exception_status: 0,
+            identification: DeviceIdentification::default(),
+        }
+    }
+}
+
+impl Default for ModbusContext {
+    fn default() -> Self {
+        ModbusContext::new()
+    }
+}
+
+/// Get a single register value
+pub fn get(reg: u16, reg_context: &[u16; CONTEXT_SIZE]) -> Result<u16, ()> {
+    let reg = reg as usize;
+    if reg >= CONTEXT_SIZE {
+        return Err(());
+    }
+    Ok(reg_context[reg])
+}
+
+/// Set a single register / coil value
+pub fn set<T: Copy>(reg: u16, value: T, reg_context: &mut [T; CONTEXT_SIZE]) -> Result<(), ()> {
+    let reg = reg as usize;
+    if reg >= CONTEXT_SIZE {
+        return Err(());
+    }
+    reg_context[reg] = value;
+    Ok(())
+}
+
+/// Bulk-set a block of registers starting at `reg`
+pub fn set_bulk(reg: u16, values: &[u16], reg_context: &mut [u16; CONTEXT_SIZE]) -> Result<(), ()> {
+    let reg = reg as usize;
+    if reg + values.len() > CONTEXT_SIZE {
+        return Err(());
+    }
+    reg_context[reg..reg + values.len()].copy_from_slice(values);
+    Ok(())
+}
+
+/// Read an IEEE754 f32 stored in two consecutive holding/input registers (big-endian)
+pub fn get_f32(reg: u16, reg_context: &[u16; CONTEXT_SIZE]) -> Result<f32, ()> {
+    let hi = get(reg, reg_context)?;
+    let lo = get(reg + 1, reg_context)?;
+    Ok(f32::from_bits(((hi as u32) << 16) | lo as u32))
+}
+
+/// Write an IEEE754 f32 into two consecutive registers (big-endian)
+pub fn set_f32(reg: u16, value: f32, reg_context: &mut [u16; CONTEXT_SIZE]) -> Result<(), ()> {
+    let bits = value.to_bits();
+    set_bulk(reg, &[(bits >> 16) as u16, bits as u16], reg_context)
+}
+
+/// Read a u32 stored in two consecutive registers (big-endian)
+pub fn get_u32(reg: u16, reg_context: &[u16; CONTEXT_SIZE]) -> Result<u32, ()> {
+    let hi = get(reg, reg_context)?;
+    let lo = get(reg + 1, reg_context)?;
+    Ok(((hi as u32) << 16) | lo as u32)
+}
+
+/// Write a u32 into two consecutive registers (big-endian)
+pub fn set_u32(reg: u16, value: u32, reg_context: &mut [u16; CONTEXT_SIZE]) -> Result<(), ()> {
+    set_bulk(reg, &[(value >> 16) as u16, value as u16], reg_context)
+}
+
+/// Read `count` coils / discretes packed into the Modbus wire format (LSB first)
+#[cfg(feature = "std")]
+pub fn get_bools_as_u8(
+    reg: u16,
+    count: u16,
+    reg_context: &[bool; CONTEXT_SIZE],
+) -> Result<Vec<u8>, ()> {
+    let reg = reg as usize;
+    let count = count as usize;
+    if reg + count > CONTEXT_SIZE {
+        return Err(());
+    }
+    let mut result: Vec<u8> = Vec::new();
+    let mut byte: u8 = 0;
+    let mut bit: u8 = 0;
+    for state in &reg_context[reg..reg + count] {
+        if *state {
+            byte |= 1 << bit;
+        }
+        bit += 1;
+        if bit > 7 {
+            result.push(byte);
+            byte = 0;
+            bit = 0;
+        }
+    }
+    if bit > 0 {
+        result.push(byte);
+    }
+    Ok(result)
+}
+
+/// Read `count` registers packed into the Modbus wire format (big-endian)
+#[cfg(feature = "std")]
+pub fn get_regs_as_u8(
+    reg: u16,
+    count: u16,
+    reg_context: &[u16; CONTEXT_SIZE],
+) -> Result<Vec<u8>, ()> {
+    let reg = reg as usize;
+    let count = count as usize;
+    if reg + count > CONTEXT_SIZE {
+        return Err(());
+    }
+    let mut result: Vec<u8> = Vec::new();
+    for value in &reg_context[reg..reg + count] {
+        result.extend_from_slice(&value.to_be_bytes());
+    }
+    Ok(result)
+}
+
+/// Pack `count` coils / discretes directly into a reply buffer (LSB first)
+///
+/// Allocation-free counterpart of [`get_bools_as_u8`] used by the frame processor; writes straight
+/// into the response instead of returning a `Vec`.
+pub fn get_bools_as_u8_into<B: super::ModbusResponse>(
+    reg: u16,
+    count: u16,
+    reg_context: &[bool; CONTEXT_SIZE],
+    out: &mut B,
+) -> Result<(), ()> {
+    let reg = reg as usize;
+    let count = count as usize;
+    if reg + count > CONTEXT_SIZE {
+        return Err(());
+    }
+    let mut byte: u8 = 0;
+    let mut bit: u8 = 0;
+    for state in &reg_context[reg..reg + count] {
+        if *state {
+            byte |= 1 << bit;
+        }
+        bit += 1;
+        if bit > 7 {
+            out.push(byte);
+            byte = 0;
+            bit = 0;
+        }
+    }
+    if bit > 0 {
+        out.push(byte);
+    }
+    Ok(())
+}
+
+/// Pack `count` registers directly into a reply buffer (big-endian)
+///
+/// Allocation-free counterpart of [`get_regs_as_u8`].
+pub fn get_regs_as_u8_into<B: super::ModbusResponse>(
+    reg: u16,
+    count: u16,
+    reg_context: &[u16; CONTEXT_SIZE],
+    out: &mut B,
+) -> Result<(), ()> {
+    let reg = reg as usize;
+    let count = count as usize;
+    if reg + count > CONTEXT_SIZE {
+        return Err(());
+    }
+    for value in &reg_context[reg..reg + count] {
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+    Ok(())
+}
+
+/// Set `count` coils from the Modbus wire format (LSB first)
+pub fn set_bools_from_u8(
+    reg: u16,
+    count: u16,
+    values: &[u8],
+    reg_context: &mut [bool; CONTEXT_SIZE],
+) -> Result<(), ()> {
+    let reg = reg as usize;
+    let count = count as usize;
+    if reg + count > CONTEXT_SIZE {
+        return Err(());
+    }
+    for i in 0..count {
+        let byte = match values.get(i / 8) {
+            Some(v) => v,
+            None => return Err(()),
+        };
+        reg_context[reg + i] = byte & (1 << (i % 8)) != 0;
+    }
+    Ok(())
+}
+
+/// Set registers from the Modbus wire format (big-endian)
+pub fn set_regs_from_u8(
+    reg: u16,
+    values: &[u8],
+    reg_context: &mut [u16; CONTEXT_SIZE],
+) -> Result<(), ()> {
+    let reg = reg as usize;
+    if reg + values.len() / 2 > CONTEXT_SIZE {
+        return Err(());
+    }
+    for (i, chunk) in values.chunks(2).enumerate() {
+        if chunk.len() < 2 {
+            return Err(());
+        }
+        reg_context[reg + i] = u16::from_be_bytes([chunk[0], chunk[1]]);
+    }
+    Ok(())
+}
+
+/// Serialize the whole register context into a flat byte blob
+#[cfg(feature = "std")]
+pub fn dump(ctx: &ModbusContext) -> Vec<u8> {
+    let mut data: Vec<u8> = Vec::new();
+    for c in ctx.coils.iter() {
+        data.push(*c as u8);
+    }
+    for d in ctx.discretes.iter() {
+        data.push(*d as u8);
+    }
+    for h in ctx.holdings.iter() {
+        data.extend_from_slice(&h.to_be_bytes());
+    }
+    for i in ctx.inputs.iter() {
+        data.extend_from_slice(&i.to_be_bytes());
+    }
+    data
+}
+
+/// Restore a register context from a blob produced by [`dump`]
+#[cfg(feature = "std")]
+pub fn restore(data: &[u8], ctx: &mut ModbusContext) -> Result<(), ()> {
+    let expected = CONTEXT_SIZE * 2 + CONTEXT_SIZE * 4;
+    if data.len() != expected {
+        return Err(());
+    }
+    let mut pos = 0;
+    for c in ctx.coils.iter_mut() {
+        *c = data[pos] != 0;
+        pos += 1;
+    }
+    for d in ctx.discretes.iter_mut() {
+        *d = data[pos] != 0;
+        pos += 1;
+    }
+    for h in ctx.holdings.iter_mut() {
+        *h = u16::from_be_bytes([data[pos], data[pos + 1]]);
+        pos += 2;
+    }
+    for i in ctx.inputs.iter_mut() {
+        *i = u16::from_be_bytes([data[pos], data[pos + 1]]);
+        pos += 2;
+    }
+    Ok(())
+}
@@ -0,0 +1,43 @@
+//! Fast and platform independent Modbus server/slave frame processor.
+//!
+//! The register map lives in a [`server::context::ModbusContext`] value the caller constructs and
+//! owns, so one process can serve several units by keeping one context per unit id. By default the
+//! crate is built with the `std` feature, which adds `Vec`-based replies and the reconnecting
+//! [`client::ModbusMaster`]. Building with `default-features = false` switches the crate to
+//! `no_std`: [`server::process_frame`] then writes its reply into a caller-supplied buffer and
+//! performs no heap allocation, so it can be driven straight from an interrupt-driven serial or
+//! Ethernet handler on bare-metal targets.
+#![cfg_attr(not(feature = "std"), no_std)]
+// The register accessors deliberately signal out-of-range with a unit error; callers only ever map
+// it to the fixed Modbus illegal-data-address code, so a richer error type would buy nothing.
+#![allow(clippy::result_unit_err)]
+
+/// Errors produced while building or parsing Modbus frames
+///
+/// The low end of the range mirrors the standard Modbus exception codes returned by a slave; the
+/// remaining variants cover framing and transport problems surfaced by the client.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum ErrorKind {
+    IllegalFunction = 0x01,
+    IllegalDataAddress = 0x02,
+    IllegalDataValue = 0x03,
+    SlaveDeviceFailure = 0x04,
+    Acknowledge = 0x05,
+    SlaveDeviceBusy = 0x06,
+    /// The reply is shorter than its own headers require
+    FrameBroken,
+    /// RTU CRC didn't match
+    FrameCRCError,
+    /// Register address / count is out of range
+    OOB,
+    /// The reply didn't match the request it answers
+    UnexpectedReply,
+    /// No reply within the read timeout
+    Timeout,
+    /// Underlying stream I/O failure
+    CommunicationError,
+}
+
+pub mod client;
+pub mod rtu;
+pub mod server;
@@ -0,0 +1,205 @@
+use crate::server::ModbusFrame;
+
+/// Source of monotonic microsecond timestamps
+///
+/// On `std` hosts use [`StdTimeSource`]; embedded users implement this over a free-running hardware
+/// timer so the frame assembler needs no `std::time`.
+pub trait TimeSource {
+    fn now_us(&self) -> u64;
+}
+
+#[cfg(feature = "std")]
+/// [`TimeSource`] backed by [`std::time::Instant`]
+pub struct StdTimeSource {
+    base: std::time::Instant,
+}
+
+#[cfg(feature = "std")]
+impl StdTimeSource {
+    pub fn new() -> Self {
+        StdTimeSource {
+            base: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for StdTimeSource {
+    fn default() -> Self {
+        StdTimeSource::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl TimeSource for StdTimeSource {
+    fn now_us(&self) -> u64 {
+        self.base.elapsed().as_micros() as u64
+    }
+}
+
+/// Reason an accumulated RTU frame was discarded
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum RtuError {
+    /// The gap between two bytes exceeded t1.5 while the frame hadn't ended
+    CharacterTimeout,
+    /// More than 256 bytes arrived without an inter-frame gap
+    FrameOverflow,
+}
+
+/// RTU frame assembler
+///
+/// Delimits frames the way the Modbus-over-serial spec requires: bytes are accumulated while the
+/// inter-character gap stays below t1.5; a gap above t1.5 before the frame ends is a character
+/// timing error and the partial frame is dropped; once t3.5 of silence follows the last byte the
+/// buffer is handed out as a complete frame for [`crate::server::process_frame`].
+///
+/// Feed every received byte through [`feed`](RtuFramer::feed) and, while the link is idle, call
+/// [`poll`](RtuFramer::poll) to detect the trailing t3.5 silence.
+pub struct RtuFramer<T: TimeSource> {
+    time: T,
+    buf: ModbusFrame,
+    len: usize,
+    last_byte_us: u64,
+    t15_us: u64,
+    t35_us: u64,
+    delivered: bool,
+}
+
+impl<T: TimeSource> RtuFramer<T> {
+    /// Create a frame assembler for the given baud rate
+    ///
+    /// One character time is `11 bits / baud`; t1.5 and t3.5 are 1.5 and 3.5 character times. For
+    /// baud rates above 19200 the spec mandates the fixed values 750 µs (t1.5) and 1750 µs (t3.5).
+    pub fn new(baud: u32, time: T) -> Self {
+        let (t15_us, t35_us) = if baud > 19200 {
+            (750, 1750)
+        } else {
+            // 11 bits per character: start + 8 data + parity + stop
+            let char_us = 11 * 1_000_000 / baud as u64;
+            (char_us * 3 / 2, char_us * 7 / 2)
+        };
+        RtuFramer {
+            time,
+            buf: [0; 256],
+            len: 0,
+            last_byte_us: 0,
+            t15_us,
+            t35_us,
+            delivered: false,
+        }
+    }
+
+    /// Feed one received byte
+    ///
+    /// Returns [`RtuError::CharacterTimeout`] when the inter-character gap exceeded t1.5 (the
+    /// partial frame is discarded and this byte starts a fresh one), or [`RtuError::FrameOverflow`]
+    /// when the frame grows past 256 bytes.
+    pub fn feed(&mut self, byte: u8) -> Result<(), RtuError> {
+        let now = self.time.now_us();
+        if self.delivered {
+            // a frame was already handed out; this byte opens the next one
+            self.reset();
+        }
+        if self.len > 0 {
+            let gap = now - self.last_byte_us;
+            if gap >= self.t35_us {
+                // a whole inter-frame gap elapsed without a poll(): drop the stale bytes
+                self.len = 0;
+            } else if gap > self.t15_us {
+                self.len = 0;
+                self.last_byte_us = now;
+                self.buf[0] = byte;
+                self.len = 1;
+                return Err(RtuError::CharacterTimeout);
+            }
+        }
+        if self.len >= self.buf.len() {
+            self.len = 0;
+            return Err(RtuError::FrameOverflow);
+        }
+        self.buf[self.len] = byte;
+        self.len += 1;
+        self.last_byte_us = now;
+        Ok(())
+    }
+
+    /// Check for the trailing t3.5 silence that terminates a frame
+    ///
+    /// Returns the accumulated frame once at least t3.5 has elapsed since the last byte. The frame
+    /// is reported exactly once; the next [`feed`](RtuFramer::feed) begins a new one.
+    pub fn poll(&mut self) -> Option<&[u8]> {
+        if self.delivered || self.len == 0 {
+            return None;
+        }
+        if self.time.now_us() - self.last_byte_us >= self.t35_us {
+            self.delivered = true;
+            return Some(&self.buf[..self.len]);
+        }
+        None
+    }
+
+    fn reset(&mut self) {
+        self.len = 0;
+        self.delivered = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    /// Manually advanced [`TimeSource`] so the timing paths are exercised deterministically
+    struct MockTime {
+        now: Cell<u64>,
+    }
+
+    impl MockTime {
+        fn new() -> Self {
+            MockTime { now: Cell::new(0) }
+        }
+        fn advance(&self, us: u64) {
+            self.now.set(self.now.get() + us);
+        }
+    }
+
+    impl TimeSource for MockTime {
+        fn now_us(&self) -> u64 {
+            self.now.get()
+        }
+    }
+
+    #[test]
+    fn fixed_timings_above_19200() {
+        let framer = RtuFramer::new(115200, MockTime::new());
+        assert_eq!(framer.t15_us, 750);
+        assert_eq!(framer.t35_us, 1750);
+    }
+
+    #[test]
+    fn complete_frame_after_t35_silence() {
+        // 9600 baud: char ~1146 µs, t3.5 ~4010 µs
+        let mut framer = RtuFramer::new(9600, MockTime::new());
+        for b in [0x01u8, 0x03, 0x00, 0x00] {
+            framer.feed(b).unwrap();
+            // inter-character gaps well below t1.5 keep the frame open
+            framer.time.advance(200);
+            assert!(framer.poll().is_none());
+        }
+        framer.time.advance(framer.t35_us);
+        assert_eq!(framer.poll(), Some([0x01u8, 0x03, 0x00, 0x00].as_slice()));
+        // the frame is reported exactly once
+        assert!(framer.poll().is_none());
+    }
+
+    #[test]
+    fn gap_above_t15_flags_character_timeout() {
+        let mut framer = RtuFramer::new(9600, MockTime::new());
+        framer.feed(0x01).unwrap();
+        framer.time.advance(framer.t15_us + 1);
+        // the stale byte is dropped and this one starts a fresh frame
+        assert_eq!(framer.feed(0x02), Err(RtuError::CharacterTimeout));
+        framer.time.advance(framer.t35_us);
+        assert_eq!(framer.poll(), Some([0x02u8].as_slice()));
+    }
+}
@@ -1,6 +1,8 @@
 #[path = "context.rs"]
 pub mod context;
 
+use context::ModbusContext;
+
 /// Standard Modbus frame
 ///
 /// As max length of Modbus frame + headers is always 256 bytes or less, the frame is a fixed [u8;
@@ -17,20 +19,131 @@ pub enum ModbusProto {
     TcpUdp,
 }
 
-fn calc_rtu_crc(frame: &[u8], data_length: u8) -> u16 {
+/// Growable reply buffer abstraction
+///
+/// The frame processor is generic over where it writes the reply, so the very same dispatcher
+/// serves both the `std` `Vec<u8>` world and the heap-less [`ModbusFrameBuf`] used on `no_std`
+/// targets. Implementors only need to offer plain byte appends.
+pub trait ModbusResponse {
+    fn clear(&mut self);
+    fn push(&mut self, byte: u8);
+    fn extend_from_slice(&mut self, data: &[u8]);
+    fn len(&self) -> usize;
+    fn as_slice(&self) -> &[u8];
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(feature = "std")]
+impl ModbusResponse for Vec<u8> {
+    fn clear(&mut self) {
+        Vec::clear(self);
+    }
+    fn push(&mut self, byte: u8) {
+        Vec::push(self, byte);
+    }
+    fn extend_from_slice(&mut self, data: &[u8]) {
+        Vec::extend_from_slice(self, data);
+    }
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+    fn as_slice(&self) -> &[u8] {
+        Vec::as_slice(self)
+    }
+}
+
+/// Maximum length of a Modbus/TCP application data unit (7-byte MBAP + 253-byte PDU)
+///
+/// A read of 125 holding registers already produces `7 + 1 + 1 + 250 = 259` bytes, which overruns
+/// the 256-byte [`ModbusFrame`]; the reply buffer is therefore sized to the full ADU maximum.
+pub const MAX_ADU_SIZE: usize = 260;
+
+/// Fixed-capacity, allocation-free reply buffer for `no_std` targets
+///
+/// Backed by a [`MAX_ADU_SIZE`]-byte array, it is large enough for any valid Modbus reply and can
+/// be reused across calls (the processor clears it before writing). The byte appends are bounds
+/// checked: writes past the capacity are dropped rather than panicking, so a malformed request can
+/// never crash the server.
+pub struct ModbusFrameBuf {
+    data: [u8; MAX_ADU_SIZE],
+    len: usize,
+}
+
+impl ModbusFrameBuf {
+    /// Create an empty reply buffer
+    pub fn new() -> Self {
+        ModbusFrameBuf {
+            data: [0; MAX_ADU_SIZE],
+            len: 0,
+        }
+    }
+}
+
+impl Default for ModbusFrameBuf {
+    fn default() -> Self {
+        ModbusFrameBuf::new()
+    }
+}
+
+impl ModbusResponse for ModbusFrameBuf {
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+    fn push(&mut self, byte: u8) {
+        if self.len < self.data.len() {
+            self.data[self.len] = byte;
+            self.len += 1;
+        }
+    }
+    fn extend_from_slice(&mut self, data: &[u8]) {
+        if self.len + data.len() <= self.data.len() {
+            self.data[self.len..self.len + data.len()].copy_from_slice(data);
+            self.len += data.len();
+        }
+    }
+    fn len(&self) -> usize {
+        self.len
+    }
+    fn as_slice(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+}
+
+/// Length of a complete Modbus TCP frame given (at least) its 6-byte MBAP header
+///
+/// Returns `6 + length`, where `length` is the MBAP length field, or `None` if fewer than 6 header
+/// bytes are available yet. A stream transport such as a smoltcp `TcpSocket` reads the header,
+/// calls this to learn how many bytes the whole frame spans, and only then hands the buffer to
+/// [`process_frame`].
+pub fn tcp_frame_len(header: &[u8]) -> Option<usize> {
+    if header.len() < 6 {
+        return None;
+    }
+    Some(6 + u16::from_be_bytes([header[4], header[5]]) as usize)
+}
+
+pub(crate) fn calc_rtu_crc(frame: &[u8], data_length: u8) -> u16 {
     let mut crc: u16 = 0xffff;
-    for pos in 0..data_length as usize {
-        crc = crc ^ frame[pos] as u16;
+    for &byte in &frame[..data_length as usize] {
+        crc ^= byte as u16;
         for _ in (0..8).rev() {
             if (crc & 0x0001) != 0 {
-                crc = crc >> 1;
-                crc = crc ^ 0xA001;
+                crc >>= 1;
+                crc ^= 0xA001;
             } else {
-                crc = crc >> 1;
+                crc >>= 1;
             }
         }
     }
-    return crc;
+    crc
+}
+
+/// Outcome of [`process`]: whether a reply was written into the response buffer
+enum ProcessOutcome {
+    Reply,
+    NoReply,
 }
 
 /// Process Modbus frame
@@ -42,15 +155,19 @@ fn calc_rtu_crc(frame: &[u8], data_length: u8) -> u16 {
 ///use std::net::UdpSocket;
 ///
 ///use rmodbus::server::{ModbusFrame, ModbusProto, process_frame};
+///use rmodbus::server::context::ModbusContext;
 ///
 ///pub fn udpserver(unit: u8, listen: &str) {
 ///    let socket = UdpSocket::bind(listen).unwrap();
+///    // the register context is owned by the server; a gateway multiplexing several unit ids
+///    // simply keeps one context per unit and picks it before dispatching
+///    let mut ctx = ModbusContext::new();
 ///    loop {
 ///        // init frame buffer
 ///        let mut buf: ModbusFrame = [0; 256];
 ///        let (_amt, src) = socket.recv_from(&mut buf).unwrap();
 ///        // Send frame for processing - modify context for write frames and get response
-///        let response: Vec<u8> = match process_frame(unit, &buf, ModbusProto::TcpUdp) {
+///        let response: Vec<u8> = match process_frame(unit, &buf, ModbusProto::TcpUdp, &mut ctx) {
 ///            Some(v) => v,
 ///            None => {
 ///                // continue loop (or exit function) if there's nothing to send as the reply
@@ -68,30 +185,71 @@ fn calc_rtu_crc(frame: &[u8], data_length: u8) -> u16 {
 /// The function returns None in cases:
 ///
 /// * **incorrect frame header**: the frame header is absolutely incorrect and there's no way to
-///     form a valid Modbus error reply
+///   form a valid Modbus error reply
 ///
 /// * **not my frame**: the specified unit id doesn't match unit id in Modbus frame
 ///
 /// * **broadcast request**: when broadcasts are processed, apps shouldn't reply anything back
 ///
-pub fn process_frame(unit_id: u8, frame: &ModbusFrame, proto: ModbusProto) -> Option<Vec<u8>> {
-    let start_frame: usize;
+#[cfg(feature = "std")]
+pub fn process_frame(
+    unit_id: u8,
+    frame: &ModbusFrame,
+    proto: ModbusProto,
+    ctx: &mut ModbusContext,
+) -> Option<Vec<u8>> {
     let mut response: Vec<u8> = Vec::new();
-    if proto == ModbusProto::TcpUdp {
+    match process(unit_id, frame, proto, ctx, &mut response) {
+        ProcessOutcome::Reply => Some(response),
+        ProcessOutcome::NoReply => None,
+    }
+}
+
+/// Process Modbus frame into a caller-supplied, allocation-free buffer (`no_std`)
+///
+/// Writes the reply into `response` and returns its length, or `None` when there's nothing to send
+/// back (see [`process_frame`] for the cases). No heap allocation is performed and the register
+/// context is taken by reference, so this can be called from a bare-metal interrupt handler.
+#[cfg(not(feature = "std"))]
+pub fn process_frame(
+    unit_id: u8,
+    frame: &ModbusFrame,
+    proto: ModbusProto,
+    ctx: &mut ModbusContext,
+    response: &mut ModbusFrameBuf,
+) -> Option<usize> {
+    match process(unit_id, frame, proto, ctx, response) {
+        ProcessOutcome::Reply => Some(response.len()),
+        ProcessOutcome::NoReply => None,
+    }
+}
+
+/// Protocol-agnostic, allocation-free frame dispatcher shared by both build modes
+fn process<B: ModbusResponse>(
+    unit_id: u8,
+    frame: &ModbusFrame,
+    proto: ModbusProto,
+    ctx: &mut ModbusContext,
+    response: &mut B,
+) -> ProcessOutcome {
+    response.clear();
+    let start_frame = if proto == ModbusProto::TcpUdp {
         //let tr_id = u16::from_be_bytes([frame[0], frame[1]]);
         let proto_id = u16::from_be_bytes([frame[2], frame[3]]);
         let length = u16::from_be_bytes([frame[4], frame[5]]);
-        if proto_id != 0 || length < 6 {
-            return None;
+        // the MBAP length counts the unit id plus the PDU, so a valid frame carries at least a unit
+        // and a function code; shorter PDUs (e.g. Read Exception Status, 0x2B) must still dispatch
+        if proto_id != 0 || length < 2 {
+            return ProcessOutcome::NoReply;
         }
-        start_frame = 6;
+        6
     } else {
-        start_frame = 0;
-    }
+        0
+    };
     let unit = frame[start_frame];
     let broadcast = unit == 0 || unit == 255; // some clients send broadcast to 0xff
     if !broadcast && unit != unit_id {
-        return None;
+        return ProcessOutcome::NoReply;
     }
     if !broadcast && proto == ModbusProto::TcpUdp {
         response.extend_from_slice(&frame[0..4]); // copy 4 bytes: tr id and proto
@@ -101,16 +259,22 @@ pub fn process_frame(unit_id: u8, frame: &ModbusFrame, proto: ModbusProto) -> Op
         ($len:expr) => {
             proto == ModbusProto::TcpUdp
                 || calc_rtu_crc(frame, $len)
-                    == u16::from_le_bytes([frame[$len as usize], frame[$len as usize + 1]]);
+                    == u16::from_le_bytes([frame[$len as usize], frame[$len as usize + 1]])
         };
     }
     macro_rules! response_error {
         ($err:expr) => {
             match proto {
-                ModbusProto::TcpUdp => {
-                    response.extend_from_slice(&[0, 3, frame[7], frame[8] + 0x80, $err])
+                ModbusProto::TcpUdp => response.extend_from_slice(&[
+                    0,
+                    3,
+                    frame[start_frame],
+                    frame[start_frame + 1] | 0x80,
+                    $err,
+                ]),
+                ModbusProto::Rtu => {
+                    response.extend_from_slice(&[frame[0], frame[1] | 0x80, $err])
                 }
-                ModbusProto::Rtu => response.extend_from_slice(&[frame[0], frame[1] + 0x80, $err]),
             }
         };
     }
@@ -125,54 +289,62 @@ pub fn process_frame(unit_id: u8, frame: &ModbusFrame, proto: ModbusProto) -> Op
         () => {
             match proto {
                 ModbusProto::Rtu => {
-                    let crc = calc_rtu_crc(&response.as_slice(), response.len() as u8);
+                    let crc = calc_rtu_crc(response.as_slice(), response.len() as u8);
                     response.extend_from_slice(&crc.to_le_bytes());
-                    Some(response)
+                    return ProcessOutcome::Reply;
                 }
-                ModbusProto::TcpUdp => Some(response),
+                ModbusProto::TcpUdp => return ProcessOutcome::Reply,
             }
         };
     }
-    if func >= 1 && func <= 4 {
+    if (1..=4).contains(&func) {
         // funcs 1 - 4
         // read coils / registers
         if broadcast || !check_frame_crc!(6) {
-            return None;
+            return ProcessOutcome::NoReply;
         }
         let count = u16::from_be_bytes([frame[start_frame + 4], frame[start_frame + 5]]);
         if ((func == 1 || func == 2) && count > 2000) || ((func == 3 || func == 4) && count > 125) {
             response_error!(0x03);
-            return finalize_response!();
+            finalize_response!();
         }
         let reg = u16::from_be_bytes([frame[start_frame + 2], frame[start_frame + 3]]);
-        let ctx = context::CONTEXT.lock().unwrap();
+        // validate the addressed range up front so the reply header can be written before the data
+        if reg as usize + count as usize > context::CONTEXT_SIZE {
+            response_error!(0x02);
+            finalize_response!();
+        }
+        let data_len = match func {
+            1 | 2 => (count as usize).div_ceil(8),
+            _ => count as usize * 2,
+        };
+        response_set_data_len!(data_len + 3);
+        // 2b unit and func
+        response.extend_from_slice(&frame[start_frame..start_frame + 2]);
+        response.push(data_len as u8);
         let result = match func {
-            1 => context::get_bools_as_u8(reg, count, &ctx.coils),
-            2 => context::get_bools_as_u8(reg, count, &ctx.discretes),
-            3 => context::get_regs_as_u8(reg, count, &ctx.holdings),
-            4 => context::get_regs_as_u8(reg, count, &ctx.inputs),
+            1 => context::get_bools_as_u8_into(reg, count, &ctx.coils, response),
+            2 => context::get_bools_as_u8_into(reg, count, &ctx.discretes, response),
+            3 => context::get_regs_as_u8_into(reg, count, &ctx.holdings, response),
+            4 => context::get_regs_as_u8_into(reg, count, &ctx.inputs, response),
             _ => panic!(), // never reaches
         };
-        drop(ctx);
         match result {
-            Ok(mut data) => {
-                response_set_data_len!(data.len() + 3);
-                // 2b unit and func
-                response.extend_from_slice(&frame[start_frame..start_frame + 2]);
-                response.push(data.len() as u8);
-                response.append(&mut data);
-                return finalize_response!();
-            }
+            Ok(_) => finalize_response!(),
             Err(_) => {
+                response.clear();
+                if !broadcast && proto == ModbusProto::TcpUdp {
+                    response.extend_from_slice(&frame[0..4]);
+                }
                 response_error!(0x02);
-                return finalize_response!();
+                finalize_response!();
             }
         }
     } else if func == 5 {
         // func 5
         // write single coil
         if !check_frame_crc!(6) {
-            return None;
+            return ProcessOutcome::NoReply;
         }
         let reg = u16::from_be_bytes([frame[start_frame + 2], frame[start_frame + 3]]);
         let val: bool;
@@ -181,96 +353,336 @@ pub fn process_frame(unit_id: u8, frame: &ModbusFrame, proto: ModbusProto) -> Op
             0x0000 => val = false,
             _ => {
                 if broadcast {
-                    return None;
+                    return ProcessOutcome::NoReply;
                 } else {
                     response_error!(0x03);
-                    return finalize_response!();
+                    finalize_response!();
                 }
             }
         };
-        let result = context::set(reg, val, &mut context::CONTEXT.lock().unwrap().coils);
+        let result = context::set(reg, val, &mut ctx.coils);
         if broadcast {
-            return None;
+            ProcessOutcome::NoReply
         } else if result.is_err() {
             response_error!(0x02);
-            return finalize_response!();
+            finalize_response!();
         } else {
             response_set_data_len!(6);
             // 6b unit, func, reg, val
             response.extend_from_slice(&frame[start_frame..start_frame + 6]);
-            return finalize_response!();
+            finalize_response!();
         }
     } else if func == 6 {
         // func 6
         // write single register
         if !check_frame_crc!(6) {
-            return None;
+            return ProcessOutcome::NoReply;
         }
         let reg = u16::from_be_bytes([frame[start_frame + 2], frame[start_frame + 3]]);
         let val = u16::from_be_bytes([frame[start_frame + 4], frame[start_frame + 5]]);
-        let result = context::set(reg, val, &mut context::CONTEXT.lock().unwrap().holdings);
+        let result = context::set(reg, val, &mut ctx.holdings);
         if broadcast {
-            return None;
+            ProcessOutcome::NoReply
         } else if result.is_err() {
             response_error!(0x02);
-            return finalize_response!();
+            finalize_response!();
         } else {
             response_set_data_len!(6);
             // 6b unit, func, reg, val
             response.extend_from_slice(&frame[start_frame..start_frame + 6]);
-            return finalize_response!();
+            finalize_response!();
         }
     } else if func == 0x0f || func == 0x10 {
         // funcs 15 & 16
         // write multiple coils / registers
         let bytes = frame[start_frame + 6];
         if !check_frame_crc!(7 + bytes) {
-            return None;
+            return ProcessOutcome::NoReply;
         }
         if bytes > 242 {
             if broadcast {
-                return None;
+                return ProcessOutcome::NoReply;
             } else {
                 response_error!(0x03);
-                return finalize_response!();
+                finalize_response!();
             }
         }
         let reg = u16::from_be_bytes([frame[start_frame + 2], frame[start_frame + 3]]);
         let count = u16::from_be_bytes([frame[start_frame + 4], frame[start_frame + 5]]);
-        let mut data: Vec<u8> = Vec::new();
-        data.extend_from_slice(&frame[start_frame + 7..start_frame + 7 + bytes as usize]);
+        let data = &frame[start_frame + 7..start_frame + 7 + bytes as usize];
         let result = match func {
-            0x0f => context::set_bools_from_u8(
-                reg,
-                count,
-                &data,
-                &mut context::CONTEXT.lock().unwrap().coils,
-            ),
-            0x10 => context::set_regs_from_u8(
-                reg,
-                &data,
-                &mut context::CONTEXT.lock().unwrap().holdings,
-            ),
+            0x0f => context::set_bools_from_u8(reg, count, data, &mut ctx.coils),
+            0x10 => context::set_regs_from_u8(reg, data, &mut ctx.holdings),
             _ => panic!(), // never reaches
         };
         if broadcast {
-            return None;
+            ProcessOutcome::NoReply
         } else {
             match result {
                 Ok(_) => {
                     response_set_data_len!(6);
                     // 6b unit, f, reg, cnt
                     response.extend_from_slice(&frame[start_frame..start_frame + 6]);
-                    return finalize_response!();
+                    finalize_response!();
                 }
                 Err(_) => {
                     response_error!(0x02);
-                    return finalize_response!();
+                    finalize_response!();
+                }
+            }
+        }
+    } else if func == 0x07 {
+        // func 7
+        // read exception status
+        if broadcast || !check_frame_crc!(2) {
+            return ProcessOutcome::NoReply;
+        }
+        response_set_data_len!(3);
+        // 2b unit and func
+        response.extend_from_slice(&frame[start_frame..start_frame + 2]);
+        response.push(ctx.exception_status);
+        finalize_response!();
+    } else if func == 0x16 {
+        // func 22
+        // mask write register
+        if !check_frame_crc!(8) {
+            return ProcessOutcome::NoReply;
+        }
+        let reg = u16::from_be_bytes([frame[start_frame + 2], frame[start_frame + 3]]);
+        let and_mask = u16::from_be_bytes([frame[start_frame + 4], frame[start_frame + 5]]);
+        let or_mask = u16::from_be_bytes([frame[start_frame + 6], frame[start_frame + 7]]);
+        match context::get(reg, &ctx.holdings) {
+            Ok(current) => {
+                let new = (current & and_mask) | (or_mask & !and_mask);
+                let _ = context::set(reg, new, &mut ctx.holdings);
+                if broadcast {
+                    return ProcessOutcome::NoReply;
+                }
+                response_set_data_len!(8);
+                // 8b unit, func, reg, and_mask, or_mask
+                response.extend_from_slice(&frame[start_frame..start_frame + 8]);
+                finalize_response!();
+            }
+            Err(_) => {
+                if broadcast {
+                    return ProcessOutcome::NoReply;
                 }
+                response_error!(0x02);
+                finalize_response!();
             }
         }
+    } else if func == 0x17 {
+        // func 23
+        // read/write multiple registers: the write block is applied first, then the read block is
+        // returned, both under the same borrow of the context so the exchange is atomic
+        if broadcast {
+            // the request carries a read block, so like funcs 1 - 4 it is ignored on broadcast
+            return ProcessOutcome::NoReply;
+        }
+        let write_bytes = frame[start_frame + 10];
+        // verify the frame is long enough to hold the write block (and, on RTU, the trailing CRC)
+        // in usize arithmetic *before* check_frame_crc! indexes past the write block - otherwise a
+        // crafted write_bytes overruns the 256-byte frame and panics
+        let crc_len = if proto == ModbusProto::Rtu { 2 } else { 0 };
+        if start_frame + 11 + write_bytes as usize + crc_len > frame.len() {
+            response_error!(0x03);
+            finalize_response!();
+        }
+        if !check_frame_crc!(11 + write_bytes) {
+            return ProcessOutcome::NoReply;
+        }
+        let read_reg = u16::from_be_bytes([frame[start_frame + 2], frame[start_frame + 3]]);
+        let read_count = u16::from_be_bytes([frame[start_frame + 4], frame[start_frame + 5]]);
+        let write_reg = u16::from_be_bytes([frame[start_frame + 6], frame[start_frame + 7]]);
+        let write_count = u16::from_be_bytes([frame[start_frame + 8], frame[start_frame + 9]]);
+        if read_count > 125 || write_count > 121 || write_bytes as u16 != write_count * 2 {
+            response_error!(0x03);
+            finalize_response!();
+        }
+        if read_reg as usize + read_count as usize > context::CONTEXT_SIZE
+            || write_reg as usize + write_count as usize > context::CONTEXT_SIZE
+        {
+            response_error!(0x02);
+            finalize_response!();
+        }
+        let data = &frame[start_frame + 11..start_frame + 11 + write_bytes as usize];
+        if context::set_regs_from_u8(write_reg, data, &mut ctx.holdings).is_err() {
+            response_error!(0x02);
+            finalize_response!();
+        }
+        let data_len = read_count as usize * 2;
+        response_set_data_len!(data_len + 3);
+        // 2b unit and func
+        response.extend_from_slice(&frame[start_frame..start_frame + 2]);
+        response.push(data_len as u8);
+        let _ = context::get_regs_as_u8_into(read_reg, read_count, &ctx.holdings, response);
+        finalize_response!();
+    } else if func == 0x2b {
+        // func 43 / MEI 0x0E
+        // encapsulated interface transport: read device identification
+        if broadcast || !check_frame_crc!(5) {
+            return ProcessOutcome::NoReply;
+        }
+        let mei_type = frame[start_frame + 2];
+        if mei_type != 0x0e {
+            response_error!(0x01);
+            finalize_response!();
+        }
+        let read_dev_id_code = frame[start_frame + 3];
+        let id = &ctx.identification;
+        let objects = [
+            id.vendor_name.as_bytes(),
+            id.product_code.as_bytes(),
+            id.major_minor_revision.as_bytes(),
+        ];
+        // unit + func + (MEI, code, conformity, more-follows, next-object-id) + number-of-objects
+        let mut data_len = 2 + 5 + 1;
+        for obj in objects.iter() {
+            data_len += 2 + obj.len();
+        }
+        response_set_data_len!(data_len);
+        response.extend_from_slice(&frame[start_frame..start_frame + 2]);
+        response.push(0x0e); // MEI type
+        response.push(read_dev_id_code);
+        response.push(0x01); // conformity level: basic identification, stream access
+        response.push(0x00); // more follows
+        response.push(0x00); // next object id
+        response.push(objects.len() as u8);
+        for (i, obj) in objects.iter().enumerate() {
+            response.push(i as u8);
+            response.push(obj.len() as u8);
+            response.extend_from_slice(obj);
+        }
+        finalize_response!();
     } else {
         response_error!(0x01);
-        return finalize_response!();
+        finalize_response!();
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use context::ModbusContext;
+
+    /// Build a Modbus/TCP frame from a PDU (function code + data), filling in the MBAP header
+    fn tcp_frame(unit: u8, pdu: &[u8]) -> ModbusFrame {
+        let mut frame = [0u8; 256];
+        frame[1] = 1; // transaction id
+        let length = (1 + pdu.len()) as u16; // unit + pdu
+        frame[4..6].copy_from_slice(&length.to_be_bytes());
+        frame[6] = unit;
+        frame[7..7 + pdu.len()].copy_from_slice(pdu);
+        frame
+    }
+
+    #[test]
+    fn read_holdings() {
+        let mut ctx = ModbusContext::new();
+        ctx.holdings[5] = 0x1234;
+        let frame = tcp_frame(1, &[0x03, 0, 5, 0, 1]);
+        let reply = process_frame(1, &frame, ModbusProto::TcpUdp, &mut ctx).unwrap();
+        assert_eq!(reply[7], 0x03);
+        assert_eq!(reply[8], 2);
+        assert_eq!(&reply[9..11], &[0x12, 0x34]);
+    }
+
+    #[test]
+    fn read_exception_status() {
+        let mut ctx = ModbusContext::new();
+        ctx.exception_status = 0x5a;
+        let frame = tcp_frame(1, &[0x07]);
+        let reply = process_frame(1, &frame, ModbusProto::TcpUdp, &mut ctx).unwrap();
+        assert_eq!(reply[7], 0x07);
+        assert_eq!(reply[8], 0x5a);
+    }
+
+    #[test]
+    fn mask_write_register() {
+        let mut ctx = ModbusContext::new();
+        ctx.holdings[4] = 0x0012;
+        // new = (0x0012 & 0x00f2) | (0x0025 & !0x00f2) = 0x0017
+        let frame = tcp_frame(1, &[0x16, 0, 4, 0x00, 0xf2, 0x00, 0x25]);
+        let reply = process_frame(1, &frame, ModbusProto::TcpUdp, &mut ctx).unwrap();
+        assert_eq!(ctx.holdings[4], 0x0017);
+        assert_eq!(reply[7], 0x16);
+        assert_eq!(&reply[8..14], &[0, 4, 0x00, 0xf2, 0x00, 0x25]);
+    }
+
+    #[test]
+    fn read_write_multiple_registers() {
+        let mut ctx = ModbusContext::new();
+        let frame = tcp_frame(
+            1,
+            &[0x17, 0, 10, 0, 2, 0, 10, 0, 2, 4, 0xaa, 0xaa, 0xbb, 0xbb],
+        );
+        let reply = process_frame(1, &frame, ModbusProto::TcpUdp, &mut ctx).unwrap();
+        // the write block is applied, then the same registers are read back
+        assert_eq!(ctx.holdings[10], 0xaaaa);
+        assert_eq!(ctx.holdings[11], 0xbbbb);
+        assert_eq!(reply[7], 0x17);
+        assert_eq!(reply[8], 4);
+        assert_eq!(&reply[9..13], &[0xaa, 0xaa, 0xbb, 0xbb]);
+    }
+
+    #[test]
+    fn read_write_multiple_registers_byte_count_mismatch() {
+        let mut ctx = ModbusContext::new();
+        // write_count = 2 but byte count = 2 (should be 4) -> illegal data value
+        let frame = tcp_frame(1, &[0x17, 0, 10, 0, 2, 0, 10, 0, 2, 2, 0xaa, 0xaa]);
+        let reply = process_frame(1, &frame, ModbusProto::TcpUdp, &mut ctx).unwrap();
+        assert_eq!(reply[7], 0x17 | 0x80);
+        assert_eq!(reply[8], 0x03);
+    }
+
+    #[test]
+    fn read_write_multiple_registers_out_of_range() {
+        let mut ctx = ModbusContext::new();
+        // read_reg 0xffff + count 1 is past CONTEXT_SIZE -> illegal data address
+        let frame = tcp_frame(1, &[0x17, 0xff, 0xff, 0, 1, 0, 10, 0, 1, 2, 0, 0]);
+        let reply = process_frame(1, &frame, ModbusProto::TcpUdp, &mut ctx).unwrap();
+        assert_eq!(reply[7], 0x17 | 0x80);
+        assert_eq!(reply[8], 0x02);
+    }
+
+    #[test]
+    fn read_write_multiple_registers_rtu_oversized_does_not_panic() {
+        // PoC: a byte count large enough to index frame[256] via the CRC read must be rejected
+        // before any indexing rather than panicking
+        let mut ctx = ModbusContext::new();
+        let mut frame = [0u8; 256];
+        frame[0] = 1;
+        frame[1] = 0x17;
+        frame[10] = 244;
+        let reply = process_frame(1, &frame, ModbusProto::Rtu, &mut ctx).unwrap();
+        assert_eq!(reply[1], 0x17 | 0x80);
+        assert_eq!(reply[2], 0x03);
+    }
+
+    #[test]
+    fn read_device_identification() {
+        let mut ctx = ModbusContext::new();
+        ctx.identification.vendor_name = "acme";
+        let frame = tcp_frame(1, &[0x2b, 0x0e, 0x01, 0x00]);
+        let reply = process_frame(1, &frame, ModbusProto::TcpUdp, &mut ctx).unwrap();
+        assert_eq!(reply[7], 0x2b);
+        assert_eq!(reply[8], 0x0e);
+        assert_eq!(reply[13], 3); // number of objects
+        // the first object is the vendor name
+        assert_eq!(reply[14], 0); // object id 0
+        assert_eq!(reply[15], 4); // length of "acme"
+        assert_eq!(&reply[16..20], b"acme");
+    }
+
+    #[test]
+    fn unsupported_mei_does_not_panic() {
+        // PoC: an unsupported MEI type over TCP hit the error arm with frame[8] >= 0x80, which
+        // panicked on `frame[8] + 0x80`; it must now return a well-formed illegal-function reply
+        let mut ctx = ModbusContext::new();
+        let frame = tcp_frame(1, &[0x2b, 0x80, 0x01]);
+        let reply = process_frame(1, &frame, ModbusProto::TcpUdp, &mut ctx).unwrap();
+        assert_eq!(reply[6], 1); // unit id echoed in the unit slot
+        assert_eq!(reply[7], 0x2b | 0x80); // func | 0x80 in the func slot
+        assert_eq!(reply[8], 0x01);
     }
 }